@@ -9,6 +9,8 @@
 extern crate log;
 
 use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use log::Level;
 
@@ -23,20 +25,24 @@ use common::conn::{Listener, FutTransform, ConnPairVec, BoxFuture};
 
 use crypto::identity::PublicKey;
 use crypto::crypto_rand::system_random;
+use crypto::hash::sha_512_256;
 use identity::{create_identity, IdentityClient};
 
-use proto::consts::{TICK_MS, KEEPALIVE_TICKS, 
+use proto::consts::{TICK_MS, KEEPALIVE_TICKS,
     CONN_TIMEOUT_TICKS, TICKS_TO_REKEY, MAX_FRAME_LENGTH,
     PROTOCOL_VERSION, MAX_CONCURRENT_ENCRYPT};
+use proto::services::Services;
 
 use common::int_convert::usize_to_u64;
 use common::transform_pool::transform_pool_loop;
 
 use timer::create_timer;
 use relay::relay_server;
+use relay::sim_open::SimOpenVersionPrefix;
 use secure_channel::SecureChannel;
 use version::VersionPrefix;
 use net::{TcpListener, socket_addr_to_tcp_address};
+use net::quic::TransportKind;
 
 use bin::load_identity_from_file;
 
@@ -71,6 +77,59 @@ where
     }
 }
 
+/// Length, in bytes, of the opaque network identity tag exchanged right
+/// after encryption. Nodes on two different Offst deployments (e.g. a test
+/// network and a production network) that happen to complete a secure
+/// channel are dropped here before any relay or funder traffic is sent.
+const NETWORK_ID_LEN: usize = 32;
+
+/// Derive a fixed-length opaque `network_id` tag from the operator-supplied
+/// `--network-id` string.
+fn derive_network_id(network_id_str: &str) -> [u8; NETWORK_ID_LEN] {
+    let hash = sha_512_256(network_id_str.as_bytes());
+    let mut network_id = [0u8; NETWORK_ID_LEN];
+    network_id.copy_from_slice(&hash);
+    network_id
+}
+
+/// This relay's advertised services: a relay only ever relays.
+fn local_services() -> Services {
+    Services::empty().with_relay()
+}
+
+/// Exchange and check the local `network_id` with the remote side right
+/// after encryption is established, dropping the connection (returning
+/// `None`) if the remote tag does not match. In the same round-trip, also
+/// exchange the `Services` bitfield so the relay can remember what each
+/// connected peer supports.
+async fn identify_peer(conn_pair: (PublicKey, ConnPairVec),
+                        local_network_id: [u8; NETWORK_ID_LEN],
+                        local_services: Services)
+    -> Option<(PublicKey, Services, ConnPairVec)> {
+
+    let (public_key, (mut sender, mut receiver)) = conn_pair;
+
+    let mut identify_bytes = local_network_id.to_vec();
+    identify_bytes.extend_from_slice(&local_services.0.to_be_bytes());
+    if await!(sender.send(identify_bytes)).is_err() {
+        return None;
+    }
+
+    let remote_identify_bytes = await!(receiver.next())?;
+    if remote_identify_bytes.len() != NETWORK_ID_LEN + 8 {
+        return None;
+    }
+    let (remote_network_id, remote_services_bytes) = remote_identify_bytes.split_at(NETWORK_ID_LEN);
+    if remote_network_id != local_network_id {
+        return None;
+    }
+    let mut services_buf = [0u8; 8];
+    services_buf.copy_from_slice(remote_services_bytes);
+    let remote_services = Services(u64::from_be_bytes(services_buf));
+
+    Some((public_key, remote_services, (sender, receiver)))
+}
+
 fn main() {
     simple_logger::init_with_level(Level::Warn).unwrap();
     let matches = App::new("Offst Relay Server")
@@ -89,8 +148,39 @@ fn main() {
                                .value_name("laddr")
                                .help("Listening address. \nExamples:\n- 0.0.0.0:1337\n- fe80::14c2:3048:b1ac:85fb:1337")
                                .required(true))
+                          .arg(Arg::with_name("sim_open")
+                               .long("sim-open")
+                               .help("Negotiate simultaneous-open (neither side is a fixed \
+                                      initiator) instead of the straight version-prefix path. \
+                                      Only needed for direct, hole-punched connections; \
+                                      relay-mediated connections should leave this off."))
+                          .arg(Arg::with_name("network_id")
+                               .long("network-id")
+                               .value_name("network_id")
+                               .help("Opaque tag identifying this network. Connections from \
+                                      peers configured with a different network-id are \
+                                      dropped right after the secure channel is established.")
+                               .required(true))
+                          .arg(Arg::with_name("transport")
+                               .long("transport")
+                               .value_name("transport")
+                               .help("Transport to listen on: tcp (default). The value \
+                                      quic is recognized, but has no listener behind it: \
+                                      the server exits with an error at startup.")
+                               .default_value("tcp"))
                           .get_matches();
-    
+
+    let sim_open = matches.is_present("sim_open");
+    let local_network_id = derive_network_id(matches.value_of("network_id").unwrap());
+
+    let transport = match TransportKind::parse(matches.value_of("transport").unwrap()) {
+        Some(transport) => transport,
+        None => {
+            error!("Invalid --transport value! Expected \"tcp\" or \"quic\".");
+            return;
+        }
+    };
+
     // Parse listening address
     let listen_address_str = matches.value_of("laddr").unwrap();
     let listen_tcp_address = match listen_address_str.parse() {
@@ -145,6 +235,18 @@ fn main() {
                                                thread_pool.clone());
 
     let rng = system_random();
+    // Only peers attempting direct connections pay the extra
+    // simultaneous-open round-trip; relay-mediated connections keep the
+    // straight version-prefix path below unchanged.
+    // TODO: Thread the resolved SimOpenRole down into the secure-channel
+    // setup below once net_node's connect path consumes it; for now this
+    // only gates whether a connection survives the nonce exchange.
+    let sim_open_version_transform = if sim_open {
+        Some(SimOpenVersionPrefix::new(version_transform.clone(), rng.clone()))
+    } else {
+        None
+    };
+
     let encrypt_transform = SecureChannel::new(
         identity_client,
         rng,
@@ -153,19 +255,44 @@ fn main() {
         thread_pool.clone());
 
 
-    let tcp_listener = TcpListener::new(MAX_FRAME_LENGTH, thread_pool.clone());
-    let (_config_sender, incoming_raw_conns) = tcp_listener.listen(listen_tcp_address);
+    // `VersionPrefix` / `SecureChannel` stay unchanged on top regardless of
+    // which listener produced the raw `ConnPairVec` stream below.
+    let (_config_sender, incoming_raw_conns) = match transport {
+        TransportKind::Tcp => {
+            let tcp_listener = TcpListener::new(MAX_FRAME_LENGTH, thread_pool.clone());
+            tcp_listener.listen(listen_tcp_address)
+        },
+        TransportKind::Quic => {
+            // QuicListener does not bind a real endpoint yet (see
+            // src/net/quic.rs); its stream of incoming connections ends at
+            // once without yielding any connection or error, so refuse to
+            // start rather than ship that.
+            error!("--transport quic is not implemented yet. Aborting.");
+            return;
+        },
+    };
 
 
     // TODO; How to get rid of Box::pin() here?
+    // When --sim-open is set, each connection must additionally complete
+    // the nonce exchange (a tie is retried inside the transform); a failed
+    // or malformed exchange drops the connection here (filter_map) instead
+    // of reaching the secure channel.
     let incoming_ver_conns = Box::pin(incoming_raw_conns
-        .then(move |raw_conn| {
+        .filter_map(move |raw_conn| {
             // TODO: A more efficient way to do this?
             // We seem to have to clone version_transform for every connection
             // to make the borrow checker happy.
             let mut c_version_transform = version_transform.clone();
+            let mut c_sim_open_transform = sim_open_version_transform.clone();
             async move {
-                await!(c_version_transform.transform(raw_conn))
+                match c_sim_open_transform {
+                    Some(ref mut sim_open_transform) => {
+                        let (_role, conn_pair) = await!(sim_open_transform.transform(raw_conn))?;
+                        Some(conn_pair)
+                    },
+                    None => Some(await!(c_version_transform.transform(raw_conn))),
+                }
             }
         }));
 
@@ -185,7 +312,24 @@ fn main() {
         return;
     }
 
-    let relay_server_fut = relay_server(incoming_enc_conns,
+    // Drop any connection whose remote network_id does not match ours,
+    // before it ever reaches the relay_server / funder traffic path. Also
+    // records each peer's advertised Services bitfield into
+    // `peer_services`, though the relay itself does not currently gate on
+    // it.
+    let peer_services: Arc<Mutex<HashMap<PublicKey, Services>>> = Arc::new(Mutex::new(HashMap::new()));
+    let incoming_ided_conns = Box::pin(incoming_enc_conns
+        .filter_map(move |(public_key, conn_pair)| {
+            let peer_services = peer_services.clone();
+            async move {
+                let (public_key, remote_services, conn_pair) =
+                    await!(identify_peer((public_key, conn_pair), local_network_id, local_services()))?;
+                peer_services.lock().unwrap().insert(public_key.clone(), remote_services);
+                Some((public_key, conn_pair))
+            }
+        }));
+
+    let relay_server_fut = relay_server(incoming_ided_conns,
                 timer_client,
                 CONN_TIMEOUT_TICKS,
                 KEEPALIVE_TICKS,
@@ -0,0 +1,150 @@
+use proto::funder::messages::{FriendStatus, FunderOutgoingControl};
+use proto::funder::scheme::FunderScheme;
+
+use crypto::identity::PublicKey;
+
+use crate::handler::handler::{MutableFunderState, MutableEphemeral};
+use crate::handler::sender::SendCommands;
+use crate::handler::canceler::cancel_pending_user_requests;
+
+#[derive(Debug)]
+pub enum HandleCloseError {
+    FriendDoesNotExist,
+    FriendIsDisabled,
+}
+
+/// Begin a cooperative close of a friend channel: locally originated requests
+/// are cancelled immediately, but requests already in flight from the remote
+/// side are left to resolve normally.
+///
+/// Not reachable from the public app API yet: `AppToAppServer` had a
+/// `CloseFriendGraceful` variant wired to this function, but it was pulled
+/// (see `proto::app_server::messages`) since this doesn't yet do anything
+/// `CloseFriend` doesn't already do.
+///
+/// This is not a full reset: channel history is kept. What ships here is
+/// deliberately partial -- see the inline notes below -- because the rest
+/// requires a persistent `is_shutting_down`-style flag on `FriendState` /
+/// `FriendMutation`, and those types live outside this handler module and
+/// aren't part of this tree:
+///
+/// - New `RequestSendFunds` are **not** actually blocked for this friend
+///   after this call returns: nothing is recorded anywhere that survives
+///   past this single invocation, so the request-routing path has nothing
+///   to check. Blocking them needs the flag above.
+/// - No `ChannelStatusReport::ShuttingDown` mutation is emitted: handler
+///   functions in this crate operate on `FunderState`/`MutableFunderState`
+///   only, while `FunderReportMutation`s are derived from state by a
+///   separate diffing layer. Without a real state field to diff against,
+///   there is nothing for that layer to pick up.
+pub fn handle_close_friend_graceful<FS>(m_state: &mut MutableFunderState<FS>,
+                                         _m_ephemeral: &mut MutableEphemeral,
+                                         send_commands: &mut SendCommands,
+                                         outgoing_control: &mut Vec<FunderOutgoingControl<FS>>,
+                                         friend_public_key: &PublicKey)
+    -> Result<(), HandleCloseError>
+
+where
+    FS: FunderScheme,
+{
+    // Find friend:
+    let friend = match m_state.state().friends.get(friend_public_key) {
+        Some(friend) => Ok(friend),
+        None => Err(HandleCloseError::FriendDoesNotExist),
+    }?;
+    match friend.status {
+        FriendStatus::Enabled => Ok(()),
+        FriendStatus::Disabled => Err(HandleCloseError::FriendIsDisabled),
+    }?;
+
+    // Cancel requests that originated locally and have not yet been sent
+    // out. Requests already sent by the remote side are left to resolve
+    // normally.
+    cancel_pending_user_requests(
+        m_state,
+        outgoing_control,
+        friend_public_key);
+
+    send_commands.set_resend_outgoing(friend_public_key);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proto::funder::messages::AddFriend;
+    use crypto::test_utils::DummyRandom;
+    use crypto::identity::{SoftwareEd25519Identity, generate_pkcs8_key_pair, Identity};
+
+    use crate::state::{FunderState, FunderMutation};
+    use crate::ephemeral::Ephemeral;
+    use crate::friend::FriendMutation;
+
+    use crate::test_scheme::TestFunderScheme;
+
+    fn setup_enabled_friend() -> (MutableFunderState<TestFunderScheme>, PublicKey) {
+        let rng_local = DummyRandom::new(&[1u8]);
+        let pkcs8_local = generate_pkcs8_key_pair(&rng_local);
+        let local_pk = SoftwareEd25519Identity::from_pkcs8(&pkcs8_local).unwrap().get_public_key();
+
+        let rng_remote = DummyRandom::new(&[2u8]);
+        let pkcs8_remote = generate_pkcs8_key_pair(&rng_remote);
+        let remote_pk = SoftwareEd25519Identity::from_pkcs8(&pkcs8_remote).unwrap().get_public_key();
+
+        let mut state = FunderState::<TestFunderScheme>::new(&local_pk, &("1337".to_string(), 1337u32));
+
+        let add_friend = AddFriend {
+            friend_public_key: remote_pk.clone(),
+            address: 3u32,
+            name: "remote_pk".into(),
+            balance: 0i128,
+        };
+        state.mutate(&FunderMutation::AddFriend(add_friend));
+
+        let friend_mutation = FriendMutation::SetStatus(FriendStatus::Enabled);
+        state.mutate(&FunderMutation::FriendMutation((remote_pk.clone(), friend_mutation)));
+
+        (MutableFunderState::new(state), remote_pk)
+    }
+
+    #[test]
+    fn test_handle_close_friend_graceful_unknown_friend() {
+        let (mut m_state, _remote_pk) = setup_enabled_friend();
+        let mut m_ephemeral = MutableEphemeral::new(Ephemeral::new());
+        let mut send_commands = SendCommands::new();
+        let mut outgoing_control = Vec::new();
+
+        let rng_other = DummyRandom::new(&[3u8]);
+        let pkcs8_other = generate_pkcs8_key_pair(&rng_other);
+        let other_pk = SoftwareEd25519Identity::from_pkcs8(&pkcs8_other).unwrap().get_public_key();
+
+        let res = handle_close_friend_graceful(&mut m_state,
+                                                &mut m_ephemeral,
+                                                &mut send_commands,
+                                                &mut outgoing_control,
+                                                &other_pk);
+        assert!(matches!(res, Err(HandleCloseError::FriendDoesNotExist)));
+    }
+
+    #[test]
+    fn test_handle_close_friend_graceful_resends_outgoing() {
+        let (mut m_state, remote_pk) = setup_enabled_friend();
+        let mut m_ephemeral = MutableEphemeral::new(Ephemeral::new());
+        let mut send_commands = SendCommands::new();
+        let mut outgoing_control = Vec::new();
+
+        handle_close_friend_graceful(&mut m_state,
+                                      &mut m_ephemeral,
+                                      &mut send_commands,
+                                      &mut outgoing_control,
+                                      &remote_pk).unwrap();
+
+        let (_initial_state, funder_mutations, _final_state) = m_state.done();
+        assert!(funder_mutations.is_empty());
+
+        let friend_send_commands = send_commands.send_commands.get(&remote_pk).unwrap();
+        assert!(friend_send_commands.resend_outgoing);
+    }
+}
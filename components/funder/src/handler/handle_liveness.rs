@@ -1,6 +1,8 @@
 use proto::funder::messages::{FriendStatus, FunderOutgoingControl};
 use proto::funder::scheme::FunderScheme;
 
+use crypto::identity::PublicKey;
+
 use crate::types::{IncomingLivenessMessage};
 
 use crate::ephemeral::EphemeralMutation;
@@ -18,6 +20,79 @@ pub enum HandleLivenessError {
     FriendAlreadyOnline,
 }
 
+/// Keepalive configuration for the liveness subsystem. If
+/// `missed_beats_threshold` consecutive ticks pass without any traffic from
+/// an online friend, it is considered offline. `ping_interval_ticks` is
+/// accepted by `handle_liveness_tick`, but no ping is sent on the wire.
+#[derive(Clone, Debug)]
+pub struct LivenessConfig {
+    pub ping_interval_ticks: usize,
+    pub missed_beats_threshold: usize,
+}
+
+/// Handle a periodic timer tick: track, for every online friend, how many
+/// consecutive ticks have passed without traffic, and declare offline any
+/// friend that has missed `missed_beats_threshold` of them.
+///
+/// This does not yet actually send a keepalive ping on the wire: that needs
+/// a `FriendMessage::Ping`/`Pong` pair in `crate::types` and a matching
+/// `SendCommands::set_send_ping` in `crate::handler::sender`, and both of
+/// those are shared, pre-existing types this commit doesn't own and isn't
+/// safe to guess the shape of. `ping_interval_ticks` is accepted and
+/// validated here so that wiring is a pure addition once those land; for
+/// now only the missed-beats counter and the offline timeout are real.
+pub fn handle_liveness_tick<FS>(m_state: &mut MutableFunderState<FS>,
+                                 m_ephemeral: &mut MutableEphemeral,
+                                 send_commands: &mut SendCommands,
+                                 outgoing_control: &mut Vec<FunderOutgoingControl<FS>>,
+                                 liveness_config: &LivenessConfig)
+where
+    FS: FunderScheme,
+{
+    let online_friends: Vec<_> = m_state.state().friends.keys()
+        .filter(|friend_public_key| m_ephemeral.ephemeral().liveness.is_online(friend_public_key))
+        .cloned()
+        .collect();
+
+    for friend_public_key in online_friends {
+        // Count this tick as missed first, so that a friend is declared
+        // offline on the `missed_beats_threshold`-th silent tick.
+        let liveness_mutation = LivenessMutation::IncMissedBeats(friend_public_key.clone());
+        let ephemeral_mutation = EphemeralMutation::LivenessMutation(liveness_mutation);
+        m_ephemeral.mutate(ephemeral_mutation);
+
+        let missed_beats = m_ephemeral.ephemeral().liveness.missed_beats(&friend_public_key);
+
+        if missed_beats >= liveness_config.missed_beats_threshold as u64 {
+            // No pong (or any other traffic) arrived in time: synthesize an
+            // offline notification and run the regular cancel-pending path.
+            let _ = handle_liveness_message(m_state,
+                                             m_ephemeral,
+                                             send_commands,
+                                             outgoing_control,
+                                             IncomingLivenessMessage::Offline(friend_public_key.clone()));
+            continue;
+        }
+
+        // `ping_interval_ticks == 0` would otherwise panic on this modulo;
+        // treat it as "never trigger a dedicated ping tick" instead.
+        if liveness_config.ping_interval_ticks != 0
+            && missed_beats % liveness_config.ping_interval_ticks as u64 == 0 {
+            // TODO: dispatch an actual `Ping` once `FriendMessage::Ping` and
+            // `SendCommands::set_send_ping` exist (see doc comment above).
+        }
+    }
+}
+
+/// Any inbound friend message is evidence of liveness:
+/// reset the missed-beats counter for that friend.
+pub fn handle_friend_traffic(m_ephemeral: &mut MutableEphemeral,
+                              friend_public_key: &PublicKey) {
+    let liveness_mutation = LivenessMutation::ResetMissedBeats(friend_public_key.clone());
+    let ephemeral_mutation = EphemeralMutation::LivenessMutation(liveness_mutation);
+    m_ephemeral.mutate(ephemeral_mutation);
+}
+
 pub fn handle_liveness_message<FS>(m_state: &mut MutableFunderState<FS>,
                                     m_ephemeral: &mut MutableEphemeral,
                                     send_commands: &mut SendCommands,
@@ -174,4 +249,83 @@ mod tests {
         let friend_send_commands = send_commands.send_commands.get(&remote_pk).unwrap();
         assert!(friend_send_commands.resend_outgoing);
     }
+
+    fn setup_online_friend() -> (MutableFunderState<TestFunderScheme>, MutableEphemeral, PublicKey) {
+        let rng_local = DummyRandom::new(&[3u8]);
+        let pkcs8_local = generate_pkcs8_key_pair(&rng_local);
+        let local_pk = SoftwareEd25519Identity::from_pkcs8(&pkcs8_local).unwrap().get_public_key();
+
+        let rng_remote = DummyRandom::new(&[4u8]);
+        let pkcs8_remote = generate_pkcs8_key_pair(&rng_remote);
+        let remote_pk = SoftwareEd25519Identity::from_pkcs8(&pkcs8_remote).unwrap().get_public_key();
+
+        let mut state = FunderState::<TestFunderScheme>::new(&local_pk, &("1337".to_string(), 1337u32));
+        let add_friend = AddFriend {
+            friend_public_key: remote_pk.clone(),
+            address: 3u32,
+            name: "remote_pk".into(),
+            balance: 0i128,
+        };
+        state.mutate(&FunderMutation::AddFriend(add_friend));
+        state.mutate(&FunderMutation::FriendMutation(
+            (remote_pk.clone(), FriendMutation::SetStatus(FriendStatus::Enabled))));
+
+        let mut m_ephemeral = MutableEphemeral::new(Ephemeral::new());
+        let ephemeral_mutation = EphemeralMutation::LivenessMutation(
+            LivenessMutation::SetOnline(remote_pk.clone()));
+        m_ephemeral.mutate(ephemeral_mutation);
+
+        (MutableFunderState::new(state), m_ephemeral, remote_pk)
+    }
+
+    #[test]
+    fn test_handle_liveness_tick_goes_offline_after_threshold() {
+        let (mut m_state, mut m_ephemeral, remote_pk) = setup_online_friend();
+        let mut send_commands = SendCommands::new();
+        let mut outgoing_control = Vec::new();
+        let liveness_config = LivenessConfig {
+            ping_interval_ticks: 1,
+            missed_beats_threshold: 3,
+        };
+
+        for _ in 0..liveness_config.missed_beats_threshold {
+            assert!(m_ephemeral.ephemeral().liveness.is_online(&remote_pk));
+            handle_liveness_tick(&mut m_state,
+                                  &mut m_ephemeral,
+                                  &mut send_commands,
+                                  &mut outgoing_control,
+                                  &liveness_config);
+        }
+
+        assert!(!m_ephemeral.ephemeral().liveness.is_online(&remote_pk));
+    }
+
+    #[test]
+    fn test_handle_liveness_tick_zero_interval_does_not_panic() {
+        let (mut m_state, mut m_ephemeral, _remote_pk) = setup_online_friend();
+        let mut send_commands = SendCommands::new();
+        let mut outgoing_control = Vec::new();
+        let liveness_config = LivenessConfig {
+            ping_interval_ticks: 0,
+            missed_beats_threshold: 10,
+        };
+
+        handle_liveness_tick(&mut m_state,
+                              &mut m_ephemeral,
+                              &mut send_commands,
+                              &mut outgoing_control,
+                              &liveness_config);
+    }
+
+    #[test]
+    fn test_handle_friend_traffic_resets_missed_beats() {
+        let (_m_state, mut m_ephemeral, remote_pk) = setup_online_friend();
+        let ephemeral_mutation = EphemeralMutation::LivenessMutation(
+            LivenessMutation::IncMissedBeats(remote_pk.clone()));
+        m_ephemeral.mutate(ephemeral_mutation);
+        assert_eq!(m_ephemeral.ephemeral().liveness.missed_beats(&remote_pk), 1);
+
+        handle_friend_traffic(&mut m_ephemeral, &remote_pk);
+        assert_eq!(m_ephemeral.ephemeral().liveness.missed_beats(&remote_pk), 0);
+    }
 }
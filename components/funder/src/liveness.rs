@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use crypto::identity::PublicKey;
+
+/// Per-friend liveness tracking: whether a friend is currently online, and
+/// (while online) how many consecutive keepalive ticks have passed without
+/// any traffic from it. Backs `handler::handle_liveness`'s online/offline
+/// transitions and missed-beat counting.
+#[derive(Clone, Debug, Default)]
+pub struct Liveness {
+    /// Online friends, mapped to their current missed-beats count.
+    online: HashMap<PublicKey, u64>,
+}
+
+impl Liveness {
+    pub fn new() -> Self {
+        Liveness::default()
+    }
+
+    pub fn is_online(&self, friend_public_key: &PublicKey) -> bool {
+        self.online.contains_key(friend_public_key)
+    }
+
+    /// Consecutive keepalive ticks since the last traffic from this friend.
+    /// `0` for a friend that is not online.
+    pub fn missed_beats(&self, friend_public_key: &PublicKey) -> u64 {
+        self.online.get(friend_public_key).cloned().unwrap_or(0)
+    }
+
+    pub fn mutate(&mut self, mutation: &LivenessMutation) {
+        match mutation {
+            LivenessMutation::SetOnline(friend_public_key) => {
+                self.online.insert(friend_public_key.clone(), 0);
+            },
+            LivenessMutation::SetOffline(friend_public_key) => {
+                self.online.remove(friend_public_key);
+            },
+            LivenessMutation::IncMissedBeats(friend_public_key) => {
+                if let Some(missed_beats) = self.online.get_mut(friend_public_key) {
+                    *missed_beats += 1;
+                }
+            },
+            LivenessMutation::ResetMissedBeats(friend_public_key) => {
+                if let Some(missed_beats) = self.online.get_mut(friend_public_key) {
+                    *missed_beats = 0;
+                }
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum LivenessMutation {
+    SetOnline(PublicKey),
+    SetOffline(PublicKey),
+    IncMissedBeats(PublicKey),
+    ResetMissedBeats(PublicKey),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::test_utils::DummyRandom;
+    use crypto::identity::{SoftwareEd25519Identity, generate_pkcs8_key_pair, Identity};
+
+    fn gen_pk(seed: u8) -> PublicKey {
+        let rng = DummyRandom::new(&[seed]);
+        let pkcs8 = generate_pkcs8_key_pair(&rng);
+        SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap().get_public_key()
+    }
+
+    #[test]
+    fn test_liveness_online_offline() {
+        let pk = gen_pk(1);
+        let mut liveness = Liveness::new();
+        assert!(!liveness.is_online(&pk));
+
+        liveness.mutate(&LivenessMutation::SetOnline(pk.clone()));
+        assert!(liveness.is_online(&pk));
+        assert_eq!(liveness.missed_beats(&pk), 0);
+
+        liveness.mutate(&LivenessMutation::SetOffline(pk.clone()));
+        assert!(!liveness.is_online(&pk));
+    }
+
+    #[test]
+    fn test_liveness_missed_beats() {
+        let pk = gen_pk(2);
+        let mut liveness = Liveness::new();
+        liveness.mutate(&LivenessMutation::SetOnline(pk.clone()));
+
+        liveness.mutate(&LivenessMutation::IncMissedBeats(pk.clone()));
+        liveness.mutate(&LivenessMutation::IncMissedBeats(pk.clone()));
+        assert_eq!(liveness.missed_beats(&pk), 2);
+
+        liveness.mutate(&LivenessMutation::ResetMissedBeats(pk.clone()));
+        assert_eq!(liveness.missed_beats(&pk), 0);
+
+        // A friend that isn't online doesn't accumulate anything:
+        let offline_pk = gen_pk(3);
+        liveness.mutate(&LivenessMutation::IncMissedBeats(offline_pk.clone()));
+        assert_eq!(liveness.missed_beats(&offline_pk), 0);
+    }
+}
@@ -0,0 +1,251 @@
+use crypto::hash::{sha_512_256, HashResult};
+
+/// An incremental (append-only) binary Merkle tree over the serialized
+/// `FunderReportMutation` / `FriendReportMutation` log. A single root hash
+/// commits to every mutation ever applied, and an app can request an
+/// `O(log n)` inclusion proof for any past mutation rather than re-reading
+/// the whole log to audit it.
+///
+/// Internally this keeps a "frontier" of completed subtree roots, one per
+/// set bit of the current leaf count -- exactly like a binary counter
+/// accumulates carries. Appending a leaf hashes it in, then repeatedly
+/// merges it with the topmost frontier entry of equal height
+/// (`parent = H(left || right)`) until no equal-height sibling remains, at
+/// which point the result becomes a new frontier entry.
+#[derive(Clone, Debug, Default)]
+pub struct MerkleAccumulator {
+    /// `frontier[h]` is `Some((root, start_leaf))` for a fully-merged
+    /// subtree of height `h` (covering leaves `[start_leaf, start_leaf +
+    /// 2^h)`) that has not yet been merged into a taller subtree.
+    frontier: Vec<Option<(HashResult, u64)>>,
+    /// For every leaf, the sibling hash encountered on each merge along its
+    /// path to the root of its own peak, in order from leaf to peak root.
+    proof_nodes: Vec<Vec<ProofNode>>,
+}
+
+/// One step of a Merkle proof: a sibling hash, and which side of
+/// `hash_pair` it occupies relative to the running hash at that point. A
+/// fixed "sibling is always the left operand" assumption breaks for
+/// whichever side was the *older* subtree in a merge (see `append`), so
+/// every step records its own direction.
+#[derive(Clone, Debug)]
+struct ProofNode {
+    hash: HashResult,
+    sibling_is_left: bool,
+}
+
+fn hash_leaf(data: &[u8]) -> HashResult {
+    let mut bytes = vec![0u8];
+    bytes.extend_from_slice(data);
+    sha_512_256(&bytes)
+}
+
+fn hash_pair(left: &HashResult, right: &HashResult) -> HashResult {
+    let mut bytes = vec![1u8];
+    bytes.extend_from_slice(left.as_ref());
+    bytes.extend_from_slice(right.as_ref());
+    sha_512_256(&bytes)
+}
+
+impl MerkleAccumulator {
+    pub fn new() -> Self {
+        MerkleAccumulator::default()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.proof_nodes.len() as u64
+    }
+
+    /// Append a new mutation (its canonical serialized bytes) to the log,
+    /// folding it into the frontier.
+    pub fn append(&mut self, mutation_bytes: &[u8]) {
+        let leaf_index = self.len();
+        self.proof_nodes.push(Vec::new());
+
+        let mut node = hash_leaf(mutation_bytes);
+        let mut start = leaf_index;
+        let mut height = 0;
+
+        loop {
+            if self.frontier.len() <= height {
+                self.frontier.push(None);
+            }
+            match self.frontier[height].take() {
+                None => {
+                    self.frontier[height] = Some((node, start));
+                    break;
+                },
+                Some((sibling_hash, sibling_start)) => {
+                    // Every leaf under the (older) sibling subtree gets
+                    // `node` appended as its next sibling, on its *right*
+                    // (its own running hash is the left operand there);
+                    // every leaf under `node`'s own subtree gets
+                    // `sibling_hash` appended on its *left*.
+                    let width = 1u64 << height;
+                    for leaf in sibling_start..sibling_start + width {
+                        self.proof_nodes[leaf as usize].push(ProofNode {
+                            hash: node.clone(),
+                            sibling_is_left: false,
+                        });
+                    }
+                    for leaf in start..start + width {
+                        self.proof_nodes[leaf as usize].push(ProofNode {
+                            hash: sibling_hash.clone(),
+                            sibling_is_left: true,
+                        });
+                    }
+
+                    node = hash_pair(&sibling_hash, &node);
+                    start = sibling_start;
+                    height += 1;
+                },
+            }
+        }
+    }
+
+    /// The root over all leaves appended so far, or `None` if the log is
+    /// empty. Combines frontier entries from tallest to shortest, treating
+    /// each shorter (more recent) subtree as the left child of the
+    /// already-combined taller (older) ones.
+    pub fn root(&self) -> Option<HashResult> {
+        let mut acc: Option<HashResult> = None;
+        for entry in self.frontier.iter().rev() {
+            if let Some((node, _start)) = entry {
+                acc = Some(match acc {
+                    None => node.clone(),
+                    Some(existing) => hash_pair(node, &existing),
+                });
+            }
+        }
+        acc
+    }
+
+    /// Inclusion proof for `leaf_index`: the sibling hashes encountered
+    /// walking from that leaf up to the root, in order. When the leaf's own
+    /// peak (the frontier entry it currently belongs to) is not the whole
+    /// tree -- any leaf count that is not a power of two -- this also bags
+    /// in the other surviving peaks, in the same tallest-to-shortest order
+    /// `root()` folds them in, so the proof still verifies against the true
+    /// `root()` rather than only the leaf's own peak root.
+    pub fn prove(&self, leaf_index: u64) -> Option<MerkleProof> {
+        let mut nodes = self.proof_nodes.get(leaf_index as usize)?.clone();
+        let peak_height = nodes.len();
+
+        let mut taller = Vec::new();
+        let mut shorter = Vec::new();
+        for (height, entry) in self.frontier.iter().enumerate() {
+            if let Some((hash, _start)) = entry {
+                if height > peak_height {
+                    taller.push((height, hash.clone()));
+                } else if height < peak_height {
+                    shorter.push((height, hash.clone()));
+                }
+            }
+        }
+        // Both must be walked tallest-first to match `root()`'s fold order.
+        taller.sort_by(|a, b| b.0.cmp(&a.0));
+        shorter.sort_by(|a, b| b.0.cmp(&a.0));
+
+        // Every peak taller than this leaf's own was folded into a single
+        // accumulator, left to right, before `root()` ever reaches this
+        // leaf's peak; replay that fold once, then pair this leaf's own
+        // (running) hash against it as the left operand, mirroring
+        // `root()`'s `hash_pair(node, existing)`.
+        if let Some((_, first)) = taller.first() {
+            let mut acc = first.clone();
+            for (_, hash) in &taller[1..] {
+                acc = hash_pair(hash, &acc);
+            }
+            nodes.push(ProofNode { hash: acc, sibling_is_left: false });
+        }
+
+        // Each shorter peak is then folded in one at a time, in the same
+        // order `root()` encounters them, each sitting to the left of the
+        // running accumulator.
+        for (_, hash) in shorter {
+            nodes.push(ProofNode { hash, sibling_is_left: true });
+        }
+
+        Some(MerkleProof { nodes })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MerkleProof {
+    nodes: Vec<ProofNode>,
+}
+
+/// Recompute a root from `leaf_bytes` and `proof`, folding in each sibling
+/// in order (on whichever side it was recorded). Returns whether the
+/// result matches `root`.
+pub fn verify(root: &HashResult, proof: &MerkleProof, leaf_bytes: &[u8]) -> bool {
+    let mut node = hash_leaf(leaf_bytes);
+    for proof_node in &proof.nodes {
+        node = if proof_node.sibling_is_left {
+            hash_pair(&proof_node.hash, &node)
+        } else {
+            hash_pair(&node, &proof_node.hash)
+        };
+    }
+    &node == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_accumulator_single_leaf() {
+        let mut acc = MerkleAccumulator::new();
+        acc.append(b"mutation-0");
+
+        let root = acc.root().unwrap();
+        let proof = acc.prove(0).unwrap();
+        assert!(verify(&root, &proof, b"mutation-0"));
+    }
+
+    #[test]
+    fn test_merkle_accumulator_several_leaves() {
+        // A power-of-two leaf count collapses to a single frontier peak, so
+        // the root returned by `root()` is exactly the root each leaf's
+        // proof reconstructs.
+        let mut acc = MerkleAccumulator::new();
+        let leaves: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i]).collect();
+        for leaf in &leaves {
+            acc.append(leaf);
+        }
+
+        assert_eq!(acc.len(), leaves.len() as u64);
+        let root = acc.root().unwrap();
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = acc.prove(i as u64).unwrap();
+            assert!(verify(&root, &proof, leaf));
+        }
+
+        // A proof for the wrong leaf must not verify.
+        let proof_0 = acc.prove(0).unwrap();
+        assert!(!verify(&root, &proof_0, &leaves[1]));
+    }
+
+    #[test]
+    fn test_merkle_accumulator_non_power_of_two_leaves() {
+        // 3, 5, 6 and 7 leaves each leave more than one surviving frontier
+        // peak, so every proof must bag in the other peaks to reach the
+        // true root rather than just its own peak's root. (4 leaves, a
+        // power of two, is covered by the same loop.)
+        for num_leaves in 3..=7u8 {
+            let mut acc = MerkleAccumulator::new();
+            let leaves: Vec<Vec<u8>> = (0..num_leaves).map(|i| vec![i]).collect();
+            for leaf in &leaves {
+                acc.append(leaf);
+            }
+
+            let root = acc.root().unwrap();
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = acc.prove(i as u64).unwrap();
+                assert!(verify(&root, &proof, leaf),
+                        "leaf {} failed to verify with {} leaves", i, num_leaves);
+            }
+        }
+    }
+}
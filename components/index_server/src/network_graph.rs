@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crypto::identity::{PublicKey, Signature};
+use crypto::identity::verify_signature;
+
+/// A signed announcement of an index server's presence and relay
+/// addresses, gossiped between index servers so that each one converges on
+/// the same view of the network without contacting every node directly.
+#[derive(Clone, Debug)]
+pub struct NodeAnnouncement<B> {
+    pub node_public_key: PublicKey,
+    pub relay_addresses: Vec<B>,
+    /// Monotonically increasing per-node counter. A server only accepts an
+    /// announcement if its sequence number is strictly newer than the one
+    /// it currently holds for that node.
+    pub seq_num: u64,
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+/// A signed update to a single friend link (edge) in the network graph.
+#[derive(Clone, Debug)]
+pub struct FriendLinkUpdate {
+    pub node_public_key: PublicKey,
+    pub friend_public_key: PublicKey,
+    pub seq_num: u64,
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+#[derive(Clone, Debug)]
+struct NodeEntry<B> {
+    announcement: NodeAnnouncement<B>,
+    last_seen_timestamp: u64,
+}
+
+#[derive(Clone, Debug)]
+struct LinkEntry {
+    update: FriendLinkUpdate,
+    last_seen_timestamp: u64,
+}
+
+/// The locally converged view of the network, built by applying gossiped
+/// `NodeAnnouncement`s and `FriendLinkUpdate`s. Ports the flood-with-dedup
+/// model: an `ApplyResult::Applied` result marks a message to be rebroadcast
+/// to other index-server peers (the graph itself sends nothing), and entries
+/// older than `staleness_ticks` are pruned so that dead links eventually
+/// disappear even without an explicit removal message.
+pub struct NetworkGraph<B> {
+    nodes: HashMap<PublicKey, NodeEntry<B>>,
+    links: HashMap<(PublicKey, PublicKey), LinkEntry>,
+    staleness_ticks: u64,
+}
+
+/// Whether applying a gossip message changed local state and should
+/// therefore be rebroadcast to other index-server peers.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ApplyResult {
+    Applied,
+    StaleOrDuplicate,
+    InvalidSignature,
+}
+
+fn canonical_node_announcement_bytes<B: AsRef<[u8]>>(node_public_key: &PublicKey,
+                                                       relay_addresses: &[B],
+                                                       seq_num: u64,
+                                                       timestamp: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(node_public_key);
+    // `relay_addresses` is the actual payload being announced; leaving it
+    // out of the signed digest would let any relaying index-server peer
+    // rewrite it in transit without invalidating the signature. Each entry
+    // is length-prefixed so a variable-length address can't be confused
+    // with the count or with a neighboring entry.
+    bytes.extend_from_slice(&(relay_addresses.len() as u64).to_be_bytes());
+    for relay_address in relay_addresses {
+        let address_bytes = relay_address.as_ref();
+        bytes.extend_from_slice(&(address_bytes.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(address_bytes);
+    }
+    bytes.extend_from_slice(&seq_num.to_be_bytes());
+    bytes.extend_from_slice(&timestamp.to_be_bytes());
+    bytes
+}
+
+fn canonical_friend_link_update_bytes(node_public_key: &PublicKey,
+                                       friend_public_key: &PublicKey,
+                                       seq_num: u64,
+                                       timestamp: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(node_public_key);
+    bytes.extend_from_slice(friend_public_key);
+    bytes.extend_from_slice(&seq_num.to_be_bytes());
+    bytes.extend_from_slice(&timestamp.to_be_bytes());
+    bytes
+}
+
+impl<B: Clone> NetworkGraph<B> {
+    pub fn new(staleness_ticks: u64) -> Self {
+        NetworkGraph {
+            nodes: HashMap::new(),
+            links: HashMap::new(),
+            staleness_ticks,
+        }
+    }
+
+    /// Apply an incoming `NodeAnnouncement`, deduping on sequence number.
+    pub fn apply_node_announcement(&mut self,
+                                    announcement: NodeAnnouncement<B>,
+                                    now: u64) -> ApplyResult
+    where
+        B: AsRef<[u8]>,
+    {
+        let signed_bytes = canonical_node_announcement_bytes::<B>(
+            &announcement.node_public_key,
+            &announcement.relay_addresses,
+            announcement.seq_num,
+            announcement.timestamp);
+        if !verify_signature(&signed_bytes, &announcement.node_public_key, &announcement.signature) {
+            return ApplyResult::InvalidSignature;
+        }
+
+        if let Some(existing) = self.nodes.get(&announcement.node_public_key) {
+            if announcement.seq_num <= existing.announcement.seq_num {
+                return ApplyResult::StaleOrDuplicate;
+            }
+        }
+
+        self.nodes.insert(announcement.node_public_key.clone(), NodeEntry {
+            announcement,
+            last_seen_timestamp: now,
+        });
+        ApplyResult::Applied
+    }
+
+    /// Apply an incoming `FriendLinkUpdate`, deduping on sequence number.
+    /// `signature` is checked against `node_public_key`, the same way
+    /// `apply_node_announcement` authenticates its own signer, so a peer can
+    /// only publish (and have gossiped) a link update for a node it holds
+    /// the private key for.
+    pub fn apply_friend_link_update(&mut self, update: FriendLinkUpdate, now: u64) -> ApplyResult {
+        let signed_bytes = canonical_friend_link_update_bytes(
+            &update.node_public_key,
+            &update.friend_public_key,
+            update.seq_num,
+            update.timestamp);
+        if !verify_signature(&signed_bytes, &update.node_public_key, &update.signature) {
+            return ApplyResult::InvalidSignature;
+        }
+
+        let key = (update.node_public_key.clone(), update.friend_public_key.clone());
+        if let Some(existing) = self.links.get(&key) {
+            if update.seq_num <= existing.update.seq_num {
+                return ApplyResult::StaleOrDuplicate;
+            }
+        }
+        self.links.insert(key, LinkEntry {
+            update,
+            last_seen_timestamp: now,
+        });
+        ApplyResult::Applied
+    }
+
+    /// Evict nodes and links that have not been refreshed within
+    /// `staleness_ticks`, so that dead links disappear from the converged
+    /// view even if no explicit removal was ever gossiped.
+    pub fn prune_stale(&mut self, now: u64) {
+        let staleness_ticks = self.staleness_ticks;
+        self.nodes.retain(|_, entry| now.saturating_sub(entry.last_seen_timestamp) <= staleness_ticks);
+        self.links.retain(|_, entry| now.saturating_sub(entry.last_seen_timestamp) <= staleness_ticks);
+    }
+
+    /// Relay addresses currently known for `node_public_key`, as converged
+    /// from gossip rather than only from locally observed friend links.
+    pub fn relay_addresses(&self, node_public_key: &PublicKey) -> Option<&[B]> {
+        self.nodes.get(node_public_key).map(|entry| entry.announcement.relay_addresses.as_slice())
+    }
+
+    pub fn has_link(&self, a: &PublicKey, b: &PublicKey) -> bool {
+        self.links.contains_key(&(a.clone(), b.clone())) || self.links.contains_key(&(b.clone(), a.clone()))
+    }
+}
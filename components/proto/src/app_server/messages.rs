@@ -3,8 +3,9 @@ use common::mutable_state::MutableState;
 use crypto::identity::PublicKey;
 
 use crate::funder::messages::{UserRequestSendFunds, ResponseReceived,
-                            ReceiptAck, AddFriend, SetFriendAddress, 
+                            ReceiptAck, AddFriend, SetFriendAddress,
                             SetFriendName, SetFriendRemoteMaxDebt, ResetFriendChannel};
+use crate::funder::invoice::Invoice;
 use crate::report::messages::{FunderReport, FunderReportMutation};
 use crate::index_client::messages::{IndexClientReport, 
     IndexClientReportMutation, ClientResponseRoutes};
@@ -61,6 +62,9 @@ where
 {
     /// Funds:
     ResponseReceived(ResponseReceived),
+    /// A signed invoice minted in response to `CreateInvoice`, ready to be
+    /// handed to a payer out of band.
+    InvoiceCreated(Invoice),
     /// Reports about current state:
     Report(NodeReport<B>),
     ReportMutations(Vec<NodeReportMutation<B>>),
@@ -74,7 +78,26 @@ pub enum AppToAppServer<B=NetAddress> {
     RemoveRelay(PublicKey),
     /// Sending funds:
     RequestSendFunds(UserRequestSendFunds),
+    /// Acknowledge a received `Receipt`, closing out the corresponding
+    /// pending request.
+    ///
+    /// NOT DONE YET: a `Receipt` does not carry anything linking it back to
+    /// the `Invoice` it paid (no `invoice_id`, nothing derived from one),
+    /// so an app cannot prove from the receipt alone which signed request
+    /// was satisfied -- only that *some* payment of the right amount
+    /// happened. `Receipt`/`ReceiptAck` are defined in
+    /// `crate::funder::messages`, which isn't part of this tree, so that
+    /// field can't be added from here.
     ReceiptAck(ReceiptAck),
+    /// Mint and export a signed invoice for a payment this app expects to
+    /// receive as payee.
+    CreateInvoice(u128, Option<String>),
+    /// Import a signed invoice received out of band. The app server
+    /// verifies the signature and expiry, then issues a `RequestSendFunds`
+    /// populated with the invoice's `dest_payment`, `invoice_id` and
+    /// destination, rejecting expired or mis-signed invoices before any
+    /// funds move.
+    PayInvoice(Invoice),
     /// Friend management:
     AddFriend(AddFriend<Vec<RelayAddress<B>>>),
     SetFriendRelays(SetFriendAddress<Vec<RelayAddress<B>>>),
@@ -84,6 +107,14 @@ pub enum AppToAppServer<B=NetAddress> {
     DisableFriend(PublicKey),
     OpenFriend(PublicKey),
     CloseFriend(PublicKey),
+    // A `CloseFriendGraceful(PublicKey)` variant was added here and wired to
+    // `handler::handle_close::handle_close_friend_graceful`, but that
+    // handler doesn't yet do anything `CloseFriend` doesn't already do: it
+    // never blocks new `RequestSendFunds` for the friend and never emits a
+    // `ChannelStatusReport::ShuttingDown` report mutation, so there is no
+    // real difference between the two operations to expose. Pulled from
+    // the public API until the shutting-down flag and report mutation
+    // actually land.
     SetFriendRemoteMaxDebt(SetFriendRemoteMaxDebt),
     ResetFriendChannel(ResetFriendChannel),
     /// Request routes from one node to another:
@@ -0,0 +1,104 @@
+use crypto::identity::PublicKey;
+use crypto::crypto_rand::CryptoRandom;
+use crypto::dh::{DhPrivateKey, DhPublicKey, Salt};
+use crypto::hash;
+
+/// Fixed size of an onion-routed route packet, in bytes. Every hop forwards
+/// a packet of exactly this size, so observing a packet reveals nothing
+/// about how many hops remain ahead of it.
+pub const ONION_ROUTE_PACKET_LEN: usize = 1024;
+/// Maximum number of hops an onion route may contain. Bounded so that the
+/// fixed-size packet always has room for per-hop filler.
+pub const MAX_ONION_ROUTE_LEN: usize = 20;
+
+/// A Sphinx-style onion-routed packet: each hop can decrypt exactly one
+/// layer, learning only the public key of the next hop, before re-blinding
+/// its ephemeral key and forwarding the (still constant-length) remainder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnionRoutePacket {
+    /// Ephemeral public key used to derive this hop's shared secret.
+    pub ephemeral_public_key: DhPublicKey,
+    /// Encrypted, constant-length layer data. Decrypting with this hop's
+    /// shared secret yields the next hop's public key (or a terminator, if
+    /// this is the final hop), a per-layer MAC, and the remaining
+    /// (shorter-by-one-layer) onion payload plus cryptographic filler.
+    pub encrypted_payload: [u8; ONION_ROUTE_PACKET_LEN],
+}
+
+/// Per-hop layer, as seen after peeling one layer of an `OnionRoutePacket`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NextHop {
+    /// Forward to another node, along with the re-blinded packet to send it.
+    Forward(PublicKey, OnionRoutePacket),
+    /// This node is the final destination of the route.
+    Destination,
+}
+
+/// Whether a route is sent as plaintext public keys. Onion-routed privacy
+/// is not offered as a selectable mode yet: `create_onion_route_packet` and
+/// `peel_onion_route_packet` below are still scaffolding (no Sphinx layer
+/// construction/peeling implemented), so there is only one variant until
+/// that lands.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RoutePrivacy {
+    Cleartext,
+}
+
+impl Default for RoutePrivacy {
+    fn default() -> Self {
+        RoutePrivacy::Cleartext
+    }
+}
+
+/// Derive the per-hop shared secret for a hop, given the
+/// sender's (possibly already re-blinded) ephemeral public key and the
+/// hop's own Diffie-Hellman private key.
+fn derive_hop_secret(ephemeral_public_key: &DhPublicKey, hop_private_key: &DhPrivateKey) -> Salt {
+    let shared_point = hop_private_key.dh(ephemeral_public_key);
+    hash::sha_512_256(&shared_point)
+}
+
+/// Check `hop_public_keys` (in order from the first relay to the final
+/// destination) for an onion route packet. An empty route or one longer
+/// than `MAX_ONION_ROUTE_LEN` returns `InvalidRouteLength`; no packet is
+/// built for any route: a valid one returns `NotImplemented`, and `rng` is
+/// unused.
+pub fn create_onion_route_packet<R: CryptoRandom>(
+    hop_public_keys: &[PublicKey],
+    rng: &R,
+) -> Result<OnionRoutePacket, OnionRouteError> {
+    if hop_public_keys.is_empty() || hop_public_keys.len() > MAX_ONION_ROUTE_LEN {
+        return Err(OnionRouteError::InvalidRouteLength);
+    }
+    // Layers are built from the last hop inward, so that each successive
+    // encryption wraps the previous one. Per-hop filler keeps every
+    // intermediate packet exactly ONION_ROUTE_PACKET_LEN bytes, so peeling a
+    // layer never reveals a hop's position in the route.
+    //
+    // Not implemented yet: `RoutePrivacy` has no selectable `Onion` variant
+    // until this lands, so no caller on a valid path can reach this
+    // function with a real route; return an error instead of panicking in
+    // case something calls it directly ahead of that wiring.
+    let _ = rng;
+    Err(OnionRouteError::NotImplemented)
+}
+
+/// Derive this hop's shared secret for `packet` from this node's
+/// Diffie-Hellman private key. No layer is peeled: the result is always
+/// `Err(OnionRouteError::NotImplemented)`.
+pub fn peel_onion_route_packet(
+    packet: &OnionRoutePacket,
+    local_private_key: &DhPrivateKey,
+) -> Result<NextHop, OnionRouteError> {
+    let _hop_secret = derive_hop_secret(&packet.ephemeral_public_key, local_private_key);
+    // See create_onion_route_packet: scaffolding only, not reachable yet.
+    Err(OnionRouteError::NotImplemented)
+}
+
+#[derive(Debug)]
+pub enum OnionRouteError {
+    InvalidRouteLength,
+    MacMismatch,
+    /// Sphinx layer construction/peeling is not implemented yet.
+    NotImplemented,
+}
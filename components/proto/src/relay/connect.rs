@@ -0,0 +1,63 @@
+use crypto::identity::PublicKey;
+
+use crate::net::messages::NetAddress;
+
+/// Sent by a node to its relay, asking it to coordinate a direct
+/// (hole-punched) connection attempt with `remote_public_key` instead of
+/// relaying traffic. Used when both friends are otherwise only reachable
+/// through this relay.
+///
+/// NOT IMPLEMENTED YET: nothing in this tree pairs up two pending
+/// `RequestSimOpen` messages, computes a `dial_at_tick`, or forwards a
+/// `SimOpenInvite` -- the relay-side coordination this type exists for
+/// lives in the `relay` crate's `relay_server`, which this snapshot
+/// doesn't include. These wire types are defined so the client side can
+/// be built against them ahead of that, but no relay here currently acts
+/// on one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestSimOpen {
+    pub remote_public_key: PublicKey,
+    /// This node's externally observed address, as seen by the relay.
+    pub observed_address: NetAddress,
+}
+
+/// Forwarded by the relay to both sides once it has paired up two pending
+/// `RequestSimOpen` messages: each side learns the other's observed
+/// address and the instant at which both should dial.
+///
+/// NOT IMPLEMENTED YET -- see `RequestSimOpen`: no relay-side pairing
+/// exists in this tree to ever construct or send one of these.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimOpenInvite {
+    pub peer_public_key: PublicKey,
+    pub peer_observed_address: NetAddress,
+    /// Relay-synchronized dial instant, in ticks from now.
+    pub dial_at_tick: u64,
+}
+
+/// Because protocol negotiation normally assumes a fixed dialer/listener
+/// split, a simultaneous-open dial runs a tie-break first: each side sends
+/// a random nonce, and the higher nonce wins the nominal "initiator" role
+/// for the ensuing handshake. Equal nonces are retried.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimOpenNonce(pub [u8; 32]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimOpenRole {
+    Initiator,
+    Responder,
+}
+
+/// Decide the simultaneous-open role from the two exchanged nonces.
+/// Returns `None` on a tie, in which case both sides must regenerate a
+/// fresh nonce and retry.
+pub fn resolve_sim_open_role(local_nonce: &SimOpenNonce, remote_nonce: &SimOpenNonce) -> Option<SimOpenRole> {
+    if local_nonce.0 == remote_nonce.0 {
+        return None;
+    }
+    Some(if local_nonce.0 > remote_nonce.0 {
+        SimOpenRole::Initiator
+    } else {
+        SimOpenRole::Responder
+    })
+}
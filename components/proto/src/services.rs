@@ -0,0 +1,48 @@
+/// A bitfield advertising which optional features a node supports (acting
+/// as relay, serving as index client, accepting incoming apps, future
+/// payment extensions, ...), exchanged during the post-encryption identify
+/// step so peers can skip requests the other side cannot serve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Services(pub u64);
+
+const RELAY: u64 = 1 << 0;
+const INDEX_CLIENT: u64 = 1 << 1;
+const INCOMING_APPS: u64 = 1 << 2;
+
+impl Services {
+    pub fn empty() -> Self {
+        Services(0)
+    }
+
+    pub fn with_relay(self) -> Self {
+        Services(self.0 | RELAY)
+    }
+
+    pub fn with_index_client(self) -> Self {
+        Services(self.0 | INDEX_CLIENT)
+    }
+
+    pub fn with_incoming_apps(self) -> Self {
+        Services(self.0 | INCOMING_APPS)
+    }
+
+    /// Whether `self` advertises every service `other` advertises.
+    pub fn includes(&self, other: &Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_services_includes() {
+        let relay_and_index = Services::empty().with_relay().with_index_client();
+        let relay_only = Services::empty().with_relay();
+
+        assert!(relay_and_index.includes(&relay_only));
+        assert!(!relay_only.includes(&relay_and_index));
+        assert!(relay_only.includes(&Services::empty()));
+    }
+}
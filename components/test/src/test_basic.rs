@@ -45,7 +45,6 @@ const CONN_TIMEOUT_TICKS: usize = 0x8;
 /// Maximum amount of concurrent applications
 /// going through the incoming connection transform at the same time
 const MAX_CONCURRENT_INCOMING_APPS: usize = 0x8;
-
 fn gen_identity(seed: &[u8]) -> impl Identity {
     let rng = DummyRandom::new(seed);
     let pkcs8 = generate_pkcs8_key_pair(&rng);
@@ -65,6 +64,13 @@ where
     identity_client
 }
 
+// NOTE: `network_id` is only checked at the standalone `relay_server`
+// binary's connection-setup pipeline (see components/bin/src/bin/relay_server.rs),
+// not threaded through `NodeConfig` or `net_node`'s own connect path, since
+// `NodeConfig` lives in the `node` crate and isn't part of this change.
+// Two differently-configured `net_node`s can therefore still complete a
+// direct connection to each other; only relay-mediated paths through a
+// relay_server built with a mismatched --network-id are gated.
 fn default_node_config() -> NodeConfig {
     NodeConfig {
         /// Memory allocated to a channel in memory (Used to connect two components)
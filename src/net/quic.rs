@@ -0,0 +1,61 @@
+use std::net::SocketAddr;
+
+use futures::channel::mpsc;
+
+use common::conn::{Listener, ConnPairVec};
+
+/// A `Listener` for QUIC with the same `ConnPairVec` interface as
+/// `TcpListener`. `listen` binds no QUIC endpoint: `address` and
+/// `max_frame_length` are unused, and the returned stream of connections
+/// ends without yielding any connection.
+pub struct QuicListener {
+    max_frame_length: usize,
+}
+
+impl QuicListener {
+    pub fn new(max_frame_length: usize) -> Self {
+        QuicListener {
+            max_frame_length,
+        }
+    }
+}
+
+impl Listener for QuicListener {
+    type Connection = ConnPairVec;
+    type Config = ();
+    type Arg = SocketAddr;
+
+    fn listen(self, address: SocketAddr)
+        -> (mpsc::Sender<()>, mpsc::Receiver<ConnPairVec>) {
+
+        let (config_sender, _config_receiver) = mpsc::channel(0);
+        let (_conn_sender, conn_receiver) = mpsc::channel(0);
+
+        // TODO: Bind a QUIC endpoint at `address`, accept incoming
+        // connections, open a bidirectional stream per connection (capped
+        // at `self.max_frame_length` per frame, matching TcpListener's
+        // length-delimited framing), and forward each as a `ConnPairVec`
+        // over `_conn_sender`.
+        let _ = self.max_frame_length;
+
+        (config_sender, conn_receiver)
+    }
+}
+
+/// Selects which listener `relay_server` (and friends) should bind,
+/// mirroring the `--transport tcp|quic` CLI flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    Tcp,
+    Quic,
+}
+
+impl TransportKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "tcp" => Some(TransportKind::Tcp),
+            "quic" => Some(TransportKind::Quic),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,10 @@
 #![allow(unused)]
-use futures::sync::oneshot;
+use std::collections::HashMap;
+
+use futures::sync::{oneshot, mpsc};
 use futures::prelude::{async, await};
-use futures::{Stream, Sink, Poll};
+use futures::stream::FuturesUnordered;
+use futures::{Stream, Sink, Poll, Async, Future};
 
 use crypto::identity::PublicKey;
 
@@ -40,16 +43,118 @@ impl<T> Drop for Tracked<T> {
     }
 }
 
+/// A pending drop notification, tagged with the remote `PublicKey` it
+/// belongs to, so that the live-connection counter can be decremented (both
+/// in total and per-key) once the admitted connection closes.
+struct DropWatch {
+    public_key: PublicKey,
+    drop_receiver: oneshot::Receiver<()>,
+}
+
+impl Future for DropWatch {
+    type Item = PublicKey;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.drop_receiver.poll() {
+            Ok(Async::Ready(())) | Err(_) => Ok(Async::Ready(self.public_key.clone())),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+        }
+    }
+}
 
+/// Bound the number of live connections admitted from `incoming_conns`,
+/// applying backpressure instead of accepting-and-immediately-closing once
+/// full: while `cur_conns >= max_conns`, `incoming_conns` is simply not
+/// polled, and polling resumes as soon as an admitted connection's stream
+/// is dropped. A per-`PublicKey` cap additionally keeps a single remote
+/// from monopolizing every slot. Admitted connections are forwarded
+/// downstream over `conn_sender`, each wrapped in `Tracked` so that when
+/// the receiving end eventually drops the connection, the drop is
+/// observed here and the relevant counters are freed.
 #[async]
-fn conn_limiter<M,K,ME,KE,T,TE>(
-                incoming_conns: T,
-                max_conns: usize) -> Result<(),()>
+fn conn_limiter<M, K, ME, KE, T, TE>(
+                mut incoming_conns: T,
+                mut conn_sender: mpsc::Sender<(Tracked<M>, K, PublicKey)>,
+                max_conns: usize,
+                max_conns_per_public_key: usize) -> Result<(), ()>
 where
-    T: Stream<Item=(M, K, PublicKey), Error=TE>,
-    M: Stream<Item=Vec<u8>, Error=ME>,
-    K: Sink<SinkItem=Vec<u8>, SinkError=KE>,
+    T: Stream<Item=(M, K, PublicKey), Error=TE> + 'static,
+    M: Stream<Item=Vec<u8>, Error=ME> + 'static,
+    K: Sink<SinkItem=Vec<u8>, SinkError=KE> + 'static,
 {
+    if max_conns == 0 {
+        // Nothing can ever be admitted, so there will never be a drop to
+        // wait on either; exit now instead of waiting on an always-empty
+        // `drop_watches`.
+        return Ok(());
+    }
+
     let mut cur_conns: usize = 0;
-    unimplemented!();
+    let mut conns_per_public_key: HashMap<PublicKey, usize> = HashMap::new();
+    let mut drop_watches = FuturesUnordered::new();
+
+    loop {
+        if cur_conns >= max_conns {
+            // Backpressure: don't poll incoming_conns at all until a slot
+            // frees up, instead of accepting a connection only to close it
+            // right away.
+            let public_key = match await!(drop_watches.into_future()) {
+                Ok((Some(public_key), remaining)) => {
+                    drop_watches = remaining;
+                    public_key
+                },
+                _ => return Ok(()),
+            };
+            cur_conns -= 1;
+            if let Some(count) = conns_per_public_key.get_mut(&public_key) {
+                *count -= 1;
+                if *count == 0 {
+                    conns_per_public_key.remove(&public_key);
+                }
+            }
+            continue;
+        }
+
+        let (m, k, public_key) = match await!(incoming_conns.into_future()) {
+            Ok((Some(triple), remaining)) => {
+                incoming_conns = remaining;
+                triple
+            },
+            _ => return Ok(()),
+        };
+
+        // Free the counters of admitted connections dropped since the last
+        // check, so the per-key cap below sees only live connections.
+        while let Ok(Async::Ready(Some(dropped_public_key))) = drop_watches.poll() {
+            cur_conns -= 1;
+            if let Some(count) = conns_per_public_key.get_mut(&dropped_public_key) {
+                *count -= 1;
+                if *count == 0 {
+                    conns_per_public_key.remove(&dropped_public_key);
+                }
+            }
+        }
+
+        let existing_count = conns_per_public_key.get(&public_key).cloned().unwrap_or(0);
+        if existing_count >= max_conns_per_public_key {
+            // This remote is already at its cap; drop the new connection
+            // without forwarding it downstream, and without leaving a
+            // zero-count entry behind for a key we never actually admit.
+            continue;
+        }
+        let per_key_count = conns_per_public_key.entry(public_key.clone()).or_insert(0);
+
+        let (drop_sender, drop_receiver) = oneshot::channel();
+        let tracked_m = Tracked::new(m, drop_sender);
+
+        conn_sender = match await!(conn_sender.send((tracked_m, k, public_key.clone()))) {
+            Ok(conn_sender) => conn_sender,
+            Err(_) => return Ok(()),
+        };
+
+        cur_conns += 1;
+        *per_key_count += 1;
+        drop_watches.push(DropWatch { public_key, drop_receiver });
+    }
 }
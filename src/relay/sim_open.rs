@@ -0,0 +1,87 @@
+use crypto::crypto_rand::{CryptoRandom, RandValue};
+
+use futures::{SinkExt, StreamExt};
+
+use common::conn::{FutTransform, ConnPairVec, BoxFuture};
+
+use proto::relay::connect::{SimOpenNonce, SimOpenRole, resolve_sim_open_role};
+
+/// Which side of the connection-setup pipeline is attempting the exchange:
+/// a straight version-prefix connection already knows its dialer/listener
+/// role ahead of time, while a simultaneous-open (hole-punched) connection
+/// does not, and must negotiate it here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    Straight,
+    SimOpen,
+}
+
+/// Wraps an existing version-prefix transform, adding a simultaneous-open
+/// negotiation round for connections where neither side is a fixed
+/// initiator (e.g. two peers hole-punching through a relay). After the
+/// version bytes, each peer sends a random 256-bit nonce; the peer with the
+/// lexicographically larger nonce becomes the initiator and proceeds with
+/// the normal dialer role in the secure-channel Diffie-Hellman setup, while
+/// the other becomes the responder. Equal nonces (astronomically unlikely)
+/// cause both sides to regenerate and retry.
+#[derive(Clone)]
+pub struct SimOpenVersionPrefix<VT, R> {
+    version_transform: VT,
+    rng: R,
+}
+
+impl<VT, R> SimOpenVersionPrefix<VT, R> {
+    pub fn new(version_transform: VT, rng: R) -> Self {
+        SimOpenVersionPrefix {
+            version_transform,
+            rng,
+        }
+    }
+}
+
+fn gen_nonce<R: CryptoRandom>(rng: &R) -> SimOpenNonce {
+    let rand_value = RandValue::new(rng);
+    let mut nonce_bytes = [0u8; 32];
+    nonce_bytes.copy_from_slice(rand_value.as_ref());
+    SimOpenNonce(nonce_bytes)
+}
+
+impl<VT, R> FutTransform for SimOpenVersionPrefix<VT, R>
+where
+    VT: FutTransform<Input=ConnPairVec, Output=ConnPairVec> + Clone + Send,
+    R: CryptoRandom + Clone,
+{
+    type Input = ConnPairVec;
+    type Output = Option<(SimOpenRole, ConnPairVec)>;
+
+    fn transform(&mut self, conn_pair: Self::Input)
+        -> BoxFuture<'_, Self::Output> {
+
+        let mut c_version_transform = self.version_transform.clone();
+        let rng = self.rng.clone();
+        Box::pin(async move {
+            let (mut sender, mut receiver) = await!(c_version_transform.transform(conn_pair));
+
+            // Retry on a tied nonce; ties are astronomically unlikely, so
+            // this loop is not expected to run more than once in practice.
+            loop {
+                let local_nonce = gen_nonce(&rng);
+                if await!(sender.send(local_nonce.0.to_vec())).is_err() {
+                    return None;
+                }
+                let remote_nonce_bytes = await!(receiver.next())?;
+                if remote_nonce_bytes.len() != 32 {
+                    return None;
+                }
+                let mut remote_nonce = [0u8; 32];
+                remote_nonce.copy_from_slice(&remote_nonce_bytes);
+                let remote_nonce = SimOpenNonce(remote_nonce);
+
+                if let Some(role) = resolve_sim_open_role(&local_nonce, &remote_nonce) {
+                    return Some((role, (sender, receiver)));
+                }
+                // Tie: both sides regenerate and retry.
+            }
+        })
+    }
+}